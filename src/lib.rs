@@ -3,9 +3,21 @@
 /// This create contains an implementation of the [Luhn checksum
 /// algorithm](https://en.wikipedia.org/wiki/Luhn_mod_N_algorithm).  For more
 /// information, see the documentation on the `Luhn` type.
+///
+/// Enabling the optional `rand` feature adds integration with the `rand`
+/// crate for generating random, already-valid code words; see `LuhnSample`.
+/// This targets the `rand` 0.7 `Distribution`/`Uniform` API.
+#[cfg(feature = "rand")]
+extern crate rand;
+
 use std::collections::HashSet;
 use std::convert::AsRef;
 
+#[cfg(feature = "rand")]
+use rand::distributions::{Distribution, Uniform};
+#[cfg(feature = "rand")]
+use rand::Rng;
+
 
 /// The error type for this crate.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -34,7 +46,7 @@ impl Luhn {
         where S: AsRef<str>
     {
         let mut chars = alphabet.as_ref().chars().collect::<Vec<char>>();
-        if chars.len() < 1 {
+        if chars.is_empty() {
             return Err(LuhnError::EmptyString);
         }
 
@@ -54,6 +66,46 @@ impl Luhn {
         Ok(Luhn { alphabet: chars })
     }
 
+    /// Constructs a `Luhn` for the classic base-10 credit-card alphabet
+    /// (`0`-`9`).
+    ///
+    /// Since this alphabet is fixed and known ahead of time to be unique,
+    /// this constructor cannot fail and so returns a `Luhn` directly, rather
+    /// than a `Result`.
+    pub fn decimal() -> Luhn {
+        Luhn::from_unique_alphabet("0123456789")
+    }
+
+    /// Constructs a `Luhn` for the base-62 alphanumeric alphabet (`0`-`9`,
+    /// `a`-`z`, `A`-`Z`), matching the character set used by `rand`'s
+    /// `Alphanumeric` distribution.
+    pub fn alphanumeric() -> Luhn {
+        Luhn::from_unique_alphabet(
+            "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ")
+    }
+
+    /// Constructs a `Luhn` for the
+    /// [Crockford base32](https://www.crockford.com/base32.html) alphabet.
+    ///
+    /// Crockford's alphabet excludes the letters `I`, `L`, `O` and `U` (to
+    /// avoid confusion with `1`, `0` and each other), leaving 32 characters.
+    /// Note that the check character is defined by this canonical ordering,
+    /// not by Crockford's own (different) check-character scheme.
+    pub fn base32_crockford() -> Luhn {
+        Luhn::from_unique_alphabet("0123456789ABCDEFGHJKMNPQRSTVWXYZ")
+    }
+
+    /// Builds a `Luhn` from an alphabet that is already known, by
+    /// construction, to contain only unique characters.
+    ///
+    /// `codepoint_from_character` relies on binary search over a sorted
+    /// alphabet, so the characters are sorted here, same as in `new`.
+    fn from_unique_alphabet(alphabet: &str) -> Luhn {
+        let mut chars = alphabet.chars().collect::<Vec<char>>();
+        chars.sort();
+        Luhn { alphabet: chars }
+    }
+
     #[inline]
     fn codepoint_from_character(&self, ch: char) -> Result<usize, LuhnError> {
         match self.alphabet.binary_search(&ch) {
@@ -67,18 +119,15 @@ impl Luhn {
         self.alphabet[cp]
     }
 
-    /// Given an input string, generate the Luhn character.
+    /// Computes the Luhn check character from an iterator of codepoints,
+    /// without requiring the caller to have materialized the input into a
+    /// `String`.
     ///
-    /// Returns an error if the input string is empty, or contains a character
-    /// that is not in the input alphabet.
-    pub fn generate<S>(&self, s: S) -> Result<char, LuhnError>
-        where S: AsRef<str>
+    /// This is the core of the algorithm; `generate`, `generate_iter` and
+    /// `validate` are all implemented in terms of it.
+    fn checksum_from_codepoints<I>(&self, it: I) -> Result<char, LuhnError>
+        where I: Iterator<Item = Result<usize, LuhnError>>
     {
-        let s = s.as_ref();
-        if s.len() == 0 {
-            return Err(LuhnError::EmptyString);
-        }
-
         let mut factor = 1;
         let mut sum = 0;
         let n = self.alphabet.len();
@@ -86,8 +135,8 @@ impl Luhn {
         // Note: this is by-and-large a transliteration of the algorithm in the
         // Wikipedia article into Rust:
         //   https://en.wikipedia.org/wiki/Luhn_mod_N_algorithm
-        for ch in s.chars() {
-            let codepoint = try!(self.codepoint_from_character(ch));
+        for codepoint in it {
+            let codepoint = codepoint?;
 
             let mut addend = factor * codepoint;
             factor = if factor == 2 {
@@ -105,6 +154,40 @@ impl Luhn {
         Ok(self.character_from_codepoint(check_codepoint))
     }
 
+    /// Given an input string, generate the Luhn character.
+    ///
+    /// Returns an error if the input string is empty, or contains a character
+    /// that is not in the input alphabet.
+    pub fn generate<S>(&self, s: S) -> Result<char, LuhnError>
+        where S: AsRef<str>
+    {
+        let s = s.as_ref();
+        if s.is_empty() {
+            return Err(LuhnError::EmptyString);
+        }
+
+        self.generate_iter(s.chars())
+    }
+
+    /// Given an iterator of input characters, generate the Luhn character.
+    ///
+    /// This is equivalent to `generate`, but allows computing the checksum
+    /// over a stream of characters (rather than a fully materialized string)
+    /// without allocating.
+    ///
+    /// Returns an error if the iterator is empty, or yields a character that
+    /// is not in the input alphabet.
+    pub fn generate_iter<I>(&self, chars: I) -> Result<char, LuhnError>
+        where I: Iterator<Item = char>
+    {
+        let mut chars = chars.peekable();
+        if chars.peek().is_none() {
+            return Err(LuhnError::EmptyString);
+        }
+
+        self.checksum_from_codepoints(chars.map(|ch| self.codepoint_from_character(ch)))
+    }
+
     /// Validates a Luhn check character.  This assumes that the final character
     /// of the input string is the Luhn character, and it will validate that the
     /// remainder of the string is correct.
@@ -116,15 +199,15 @@ impl Luhn {
             return Err(LuhnError::EmptyString);
         }
 
-        // Extract the check character and remainder of the string.
-        // TODO: can we do this without allocating a new String?
-        let head = s.char_indices()
-                    .take_while(|&(index, _)| index < s.len() - 1)
-                    .map(|(_, ch)| ch)
-                    .collect::<String>();
+        // Feed every character except the trailing check character into the
+        // checksum routine, without allocating an intermediate String.
+        let body_len = s.chars().count() - 1;
+        let codepoints = s.chars()
+                          .take(body_len)
+                          .map(|ch| self.codepoint_from_character(ch));
+        let expected = self.checksum_from_codepoints(codepoints)?;
         let luhn = s.chars().last().unwrap();
 
-        let expected = try!(self.generate(head));
         Ok(luhn == expected)
     }
 
@@ -139,9 +222,115 @@ impl Luhn {
             return Err(LuhnError::EmptyString);
         }
 
-        let expected = try!(self.generate(s));
+        let expected = self.generate(s)?;
         Ok(check == expected)
     }
+
+    /// Appends `payload` followed by its Luhn check character onto `out`.
+    ///
+    /// This is equivalent to
+    /// `let check = self.generate(payload)?; out.push_str(payload); out.push(check)`,
+    /// so `out` is left unchanged on error, but lets high-throughput callers
+    /// reuse a single buffer (clearing it between calls) instead of
+    /// allocating a fresh `String` for every code word.
+    pub fn generate_append<S>(&self, payload: S, out: &mut String) -> Result<(), LuhnError>
+        where S: AsRef<str>
+    {
+        let payload = payload.as_ref();
+        let check = self.generate(payload)?;
+
+        out.push_str(payload);
+        out.push(check);
+        Ok(())
+    }
+
+    /// Draws `payload_len` codepoints uniformly from the alphabet and
+    /// appends the corresponding characters onto `out`, followed by the
+    /// check character for that payload.
+    ///
+    /// This is the shared core behind `sample_append` and `LuhnSample`,
+    /// so the sampling/checksum logic only lives in one place.  A
+    /// `payload_len` of zero is allowed, appending just the check character
+    /// for the empty payload.
+    #[cfg(feature = "rand")]
+    fn sample_append_codepoints<R: Rng + ?Sized>(&self, rng: &mut R, payload_len: usize, out: &mut String) {
+        let between = Uniform::new(0, self.alphabet.len());
+        let codepoints = (0..payload_len)
+            .map(|_| between.sample(rng))
+            .collect::<Vec<usize>>();
+
+        for &cp in codepoints.iter() {
+            out.push(self.character_from_codepoint(cp));
+        }
+
+        // Codepoints are all in-range by construction (they came from
+        // `between`, which is bounded by the alphabet length), so this
+        // cannot fail even when `payload_len` is zero.
+        let check = self.checksum_from_codepoints(codepoints.iter().map(|&cp| Ok(cp)))
+            .expect("codepoints are all in-range by construction");
+        out.push(check);
+    }
+
+    /// Appends a random, valid code word onto `out`, reusing its existing
+    /// allocation.
+    ///
+    /// This is the buffer-reusing counterpart of `LuhnSample`: `payload_len`
+    /// characters are drawn uniformly from the alphabet and pushed onto
+    /// `out`, followed by the check character for that payload.  A
+    /// `payload_len` of zero is allowed, appending just the check character
+    /// for the empty payload.
+    #[cfg(feature = "rand")]
+    pub fn sample_append<R: Rng>(&self, rng: &mut R, payload_len: usize, out: &mut String) {
+        self.sample_append_codepoints(rng, payload_len, out)
+    }
+
+    /// Returns an unbounded iterator of random, valid code words for this
+    /// alphabet, each of length `payload_len + 1`.
+    ///
+    /// This is the streaming counterpart of `LuhnSample`, for callers that
+    /// want to bulk-generate code words without re-implementing the sampling
+    /// loop themselves, e.g. `luhn.valid_codes(&mut rng, 15).take(1000).collect()`.
+    #[cfg(feature = "rand")]
+    pub fn valid_codes<'a, R: Rng>(&'a self, rng: &'a mut R, payload_len: usize)
+        -> impl Iterator<Item = String> + 'a
+    {
+        let sampler = LuhnSample::new(self, payload_len);
+        ::std::iter::repeat(()).map(move |_| sampler.sample(&mut *rng))
+    }
+}
+
+/// A `rand` `Distribution` that samples random strings which already carry a
+/// correct Luhn check character for `luhn`'s alphabet.
+///
+/// Each sampled string has length `payload_len + 1`: `payload_len` characters
+/// drawn uniformly from the alphabet, followed by the check character for
+/// that payload.  For a non-zero `payload_len` the result satisfies
+/// `luhn.validate(..)`.  A `payload_len` of zero is allowed, yielding just
+/// the check character for the empty payload; `validate` rejects such a
+/// one-character string with `LuhnError::EmptyString`.
+#[cfg(feature = "rand")]
+#[derive(Debug)]
+pub struct LuhnSample<'a> {
+    luhn: &'a Luhn,
+    payload_len: usize,
+}
+
+#[cfg(feature = "rand")]
+impl<'a> LuhnSample<'a> {
+    /// Constructs a sampler that draws random payloads of `payload_len`
+    /// characters from `luhn`'s alphabet.
+    pub fn new(luhn: &'a Luhn, payload_len: usize) -> LuhnSample<'a> {
+        LuhnSample { luhn, payload_len }
+    }
+}
+
+#[cfg(feature = "rand")]
+impl<'a> Distribution<String> for LuhnSample<'a> {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> String {
+        let mut payload = String::new();
+        self.luhn.sample_append_codepoints(rng, self.payload_len, &mut payload);
+        payload
+    }
 }
 
 
@@ -149,21 +338,27 @@ impl Luhn {
 mod tests {
     extern crate rand;
 
-    use self::rand::{Isaac64Rng, Rng, SeedableRng, sample, thread_rng};
+    #[cfg(feature = "rand")]
+    use self::rand::distributions::Distribution;
+    use self::rand::rngs::StdRng;
+    use self::rand::seq::SliceRandom;
+    use self::rand::{Rng, SeedableRng, thread_rng};
 
     use super::{Luhn, LuhnError};
+    #[cfg(feature = "rand")]
+    use super::LuhnSample;
 
     #[test]
     fn test_generate() {
         // Base 6
-        let l = Luhn::new("abcdef").ok().expect("valid alphabet");
+        let l = Luhn::new("abcdef").expect("valid alphabet");
 
         match l.generate("abcdef") {
             Ok(ch) => assert_eq!(ch, 'e'),
             Err(e) => panic!("unexpected generate error: {:?}", e),
         };
 
-        let l = Luhn::new("0123456789").ok().expect("valid alphabet");
+        let l = Luhn::new("0123456789").expect("valid alphabet");
 
         match l.generate("7992739871") {
             Ok(ch) => assert_eq!(ch, '3'),
@@ -181,7 +376,7 @@ mod tests {
 
     #[test]
     fn test_invalid_input() {
-        let l = Luhn::new("abcdef").ok().expect("valid alphabet");
+        let l = Luhn::new("abcdef").expect("valid alphabet");
 
         match l.generate("012345") {
             Ok(_) => panic!("unexpected success"),
@@ -191,7 +386,7 @@ mod tests {
 
     #[test]
     fn test_validate() {
-        let l = Luhn::new("abcdef").ok().expect("valid alphabet");
+        let l = Luhn::new("abcdef").expect("valid alphabet");
 
         assert!(l.validate("abcdefe").unwrap());
         assert!(!l.validate("abcdefd").unwrap());
@@ -202,7 +397,7 @@ mod tests {
         // Alphabet must have at least one character.
         assert_eq!(Luhn::new("").unwrap_err(), LuhnError::EmptyString);
 
-        let l = Luhn::new("abcdef").ok().expect("valid alphabet");
+        let l = Luhn::new("abcdef").expect("valid alphabet");
 
         // Cannot generate on an empty string.
         assert_eq!(l.generate("").unwrap_err(), LuhnError::EmptyString);
@@ -211,9 +406,20 @@ mod tests {
         assert_eq!(l.validate("a").unwrap_err(), LuhnError::EmptyString);
     }
 
+    #[test]
+    fn test_preset_alphabets() {
+        assert_eq!(Luhn::decimal().generate("7992739871"), Ok('3'));
+
+        let l = Luhn::alphanumeric();
+        assert!(l.validate(format!("abc123{}", l.generate("abc123").unwrap())).unwrap());
+
+        let l = Luhn::base32_crockford();
+        assert!(l.validate(format!("ABCDEFG{}", l.generate("ABCDEFG").unwrap())).unwrap());
+    }
+
     #[test]
     fn test_validate_with() {
-        let l = Luhn::new("abcdef").ok().expect("valid alphabet");
+        let l = Luhn::new("abcdef").expect("valid alphabet");
 
         assert!(l.validate_with("abcdef", 'e').unwrap());
         assert!(!l.validate_with("abcdef", 'd').unwrap());
@@ -222,14 +428,67 @@ mod tests {
     #[test]
     fn test_longer_input() {
         // This test caught an out-of-bounds error.
-        let l = Luhn::new("abcdef").ok().expect("valid alphabet");
+        let l = Luhn::new("abcdef").expect("valid alphabet");
         let _ = l.generate("aabbccdd");
     }
 
+    #[test]
+    fn test_generate_iter_matches_generate() {
+        let l = Luhn::new("abcdef").expect("valid alphabet");
+        assert_eq!(l.generate("abcdef"), l.generate_iter("abcdef".chars()));
+
+        let l = Luhn::new("0123456789").expect("valid alphabet");
+        assert_eq!(l.generate_iter("7992739871".chars()), Ok('3'));
+    }
+
+    #[test]
+    fn test_generate_iter_empty() {
+        let l = Luhn::new("abcdef").expect("valid alphabet");
+        assert_eq!(l.generate_iter("".chars()).unwrap_err(), LuhnError::EmptyString);
+    }
+
+    #[test]
+    fn test_generate_append() {
+        let l = Luhn::new("abcdef").expect("valid alphabet");
+        let mut out = String::from("xyz-");
+
+        l.generate_append("abcdef", &mut out).unwrap();
+        assert_eq!(out, "xyz-abcdefe");
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_sample_append() {
+        let l = Luhn::decimal();
+        let mut rng = StdRng::seed_from_u64(55);
+        let mut out = String::new();
+
+        l.sample_append(&mut rng, 15, &mut out);
+        assert_eq!(out.chars().count(), 16);
+        assert!(l.validate(&out).unwrap());
+
+        // Appending again should extend the buffer rather than replace it.
+        let before = out.clone();
+        l.sample_append(&mut rng, 15, &mut out);
+        assert!(out.starts_with(&before));
+        assert_eq!(out.chars().count(), 32);
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_sample_append_empty_payload() {
+        let l = Luhn::decimal();
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut out = String::new();
+
+        l.sample_append(&mut rng, 0, &mut out);
+        assert_eq!(out.chars().count(), 1);
+    }
+
     #[test]
     fn test_random_input() {
         const NUM_TESTS: usize = 10000;
-        const PRINTABLE: &'static str = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTU\
+        const PRINTABLE: &str = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTU\
                                          VWXYZ";
         let printable_chars = PRINTABLE.chars().collect::<Vec<char>>();
 
@@ -238,7 +497,7 @@ mod tests {
         println!("Seed for this run: {}", seed);
 
         // Create the seedable RNG with this seed.
-        let mut rng = Isaac64Rng::from_seed(&[seed]);
+        let mut rng = StdRng::seed_from_u64(seed);
 
         for i in 1..NUM_TESTS {
             // Generate a random alphabet size
@@ -246,8 +505,8 @@ mod tests {
 
             // Create the alphabet by taking this many characters from our
             // printable characters Vec.
-            let chars = sample(&mut rng, &printable_chars, alphabet_size as usize)
-                            .into_iter()
+            let chars = printable_chars
+                            .choose_multiple(&mut rng, alphabet_size as usize)
                             .cloned()
                             .collect::<Vec<char>>();
             let alphabet = chars.iter().cloned().collect::<String>();
@@ -257,7 +516,7 @@ mod tests {
 
             // Generate this many random characters.
             let input = (0..input_length)
-                            .map(|_| *rng.choose(&*chars).unwrap())
+                            .map(|_| *chars.choose(&mut rng).unwrap())
                             .collect::<String>();
 
             // Validate that this succeeds.
@@ -269,4 +528,58 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_luhn_sample_round_trips() {
+        let l = Luhn::decimal();
+        let mut rng = StdRng::seed_from_u64(42);
+        let sampler = LuhnSample::new(&l, 15);
+
+        for _ in 0..100 {
+            let code = sampler.sample(&mut rng);
+            assert_eq!(code.chars().count(), 16);
+            assert!(l.validate(&code).unwrap());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_luhn_sample_empty_payload() {
+        // A payload_len of zero is a degenerate but valid case: it should
+        // yield just the check character, not panic.
+        let l = Luhn::decimal();
+        let mut rng = StdRng::seed_from_u64(7);
+        let sampler = LuhnSample::new(&l, 0);
+
+        let code = sampler.sample(&mut rng);
+        assert_eq!(code.chars().count(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_valid_codes() {
+        let l = Luhn::decimal();
+        let mut rng = StdRng::seed_from_u64(99);
+
+        let codes = l.valid_codes(&mut rng, 15).take(100).collect::<Vec<String>>();
+        assert_eq!(codes.len(), 100);
+        for code in &codes {
+            assert_eq!(code.chars().count(), 16);
+            assert!(l.validate(code).unwrap());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_valid_codes_empty_payload() {
+        let l = Luhn::decimal();
+        let mut rng = StdRng::seed_from_u64(13);
+
+        let codes = l.valid_codes(&mut rng, 0).take(10).collect::<Vec<String>>();
+        assert_eq!(codes.len(), 10);
+        for code in &codes {
+            assert_eq!(code.chars().count(), 1);
+        }
+    }
 }